@@ -0,0 +1,46 @@
+use crate::token::TokenId;
+use near_sdk::AccountId;
+
+/// Drawing on the owner/pause/rbac components from `near-sdk-contract-tools`, this replaces the
+/// single hard-coded owner check with a governable permission system:
+/// - a `Minter` role, so minting can be delegated to more than one account;
+/// - two-step transferable ownership (`propose_owner`/`accept_owner`), so control of the contract
+///   can move without a moment where nobody, or the wrong account, holds it;
+/// - a global pause switch, for halting transfers and new escrow/order/HTLC locks during an
+///   incident (settling already-locked tokens and `mt_resolve_transfer` refunds still go through);
+/// - per-token freezing, for halting a single compromised or disputed token without pausing
+///   everything else.
+pub trait AccessControl {
+    /// Proposes `new_owner_id` as the next owner. Only the current owner may call this, and it
+    /// takes effect only once `new_owner_id` calls [`Self::accept_owner`].
+    fn propose_owner(&mut self, new_owner_id: AccountId);
+
+    /// Accepts a pending ownership transfer. Only the proposed owner may call this.
+    fn accept_owner(&mut self);
+
+    /// Grants the `Minter` role to `account_id`. Only the owner may call this.
+    fn add_minter(&mut self, account_id: AccountId);
+
+    /// Revokes the `Minter` role from `account_id`. Only the owner may call this.
+    fn remove_minter(&mut self, account_id: AccountId);
+
+    /// Returns whether `account_id` currently holds the `Minter` role.
+    fn is_minter(&self, account_id: AccountId) -> bool;
+
+    /// Pauses or unpauses transfers contract-wide. Only the owner may call this.
+    fn set_paused(&mut self, paused: bool);
+
+    /// Returns whether transfers are currently paused.
+    fn is_paused(&self) -> bool;
+
+    /// Freezes `token_id`, causing transfers of it through the public transfer methods, and new
+    /// escrow/order/HTLC locks of it, to fail until unfrozen. Settling already-locked tokens and
+    /// `mt_resolve_transfer` refunds still go through. Only the owner may call this.
+    fn freeze_token(&mut self, token_id: TokenId);
+
+    /// Unfreezes a previously frozen `token_id`. Only the owner may call this.
+    fn unfreeze_token(&mut self, token_id: TokenId);
+
+    /// Returns whether `token_id` is currently frozen.
+    fn is_token_frozen(&self, token_id: TokenId) -> bool;
+}
@@ -0,0 +1,67 @@
+use crate::token::TokenId;
+use crate::Balance;
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{ext_contract, AccountId, Promise};
+use std::collections::HashMap;
+
+/// A single operator's standing allowance over one `(token_id, owner_id)` pair.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Approval {
+    pub approval_id: u64,
+    pub amount: Balance,
+}
+
+/// All approvals an owner has granted for a single token, plus the monotonic counter used to
+/// hand out `approval_id`s so a revoked-then-reissued approval can't be replayed under its old id.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct TokenApprovals {
+    pub by_account: HashMap<AccountId, Approval>,
+    pub next_approval_id: u64,
+}
+
+/// Notifies an approved account that it has just been granted an approval, mirroring
+/// `nft_on_approve` from the NFT approval-management standard.
+#[ext_contract(ext_mt_approval_receiver)]
+pub trait MultiTokenApprovalReceiver {
+    fn mt_on_approve(
+        &mut self,
+        token_ids: Vec<TokenId>,
+        owner_id: AccountId,
+        approval_ids: Vec<u64>,
+        msg: String,
+    );
+}
+
+/// Port of the NEP-178 approval-management surface: lets an owner authorize a third-party
+/// operator to move a capped amount of a token without handing over custody.
+pub trait MultiTokenApproval {
+    /// Approves `account_id` to transfer up to `amounts[i]` of `token_ids[i]` on the caller's
+    /// behalf. Returns a promise to `mt_on_approve` on `account_id` if `msg` is set.
+    fn mt_approve(
+        &mut self,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> Option<Promise>;
+
+    /// Revokes `account_id`'s approval (if any) for each of `token_ids`.
+    fn mt_revoke(&mut self, token_ids: Vec<TokenId>, account_id: AccountId);
+
+    /// Revokes every approval the caller has granted for each of `token_ids`.
+    fn mt_revoke_all(&mut self, token_ids: Vec<TokenId>);
+
+    /// Checks that `approved_account_id` currently holds an approval from `owner_id` covering at
+    /// least `amounts[i]` of `token_ids[i]`, optionally pinned to a specific `approval_ids[i]`.
+    fn mt_is_approved(
+        &self,
+        owner_id: AccountId,
+        approved_account_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        approval_ids: Option<Vec<u64>>,
+    ) -> bool;
+}
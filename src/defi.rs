@@ -1,24 +1,22 @@
-use crate::receiver::MultiTokenReceiver;
-use crate::token::{Token, TokenId};
+use crate::token::TokenId;
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::U128;
-use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, Promise};
-use near_sdk::{ext_contract, near_bindgen, require, AccountId, PromiseOrValue};
+use near_sdk::{env, ext_contract, near_bindgen, require, AccountId};
 
-/// `escrow_transfer` has to be implemented by the MT contract and called within `mt_on_transfer` to
-/// facilitate the swap.
-/// NOTE: This is just an example interface to demonstrate swap functionality. It's NOT intended to
-/// be a part of MT spec.
-#[ext_contract(ext_defi_escrow_transfer)]
-pub trait EscrowTransfer {
-    fn escrow_transfer(
+/// The subset of the multi-token contract's approval-gated transfer surface this exchange needs
+/// to pull escrowed tokens directly out of an owner's balance, rather than requiring the owner to
+/// push them over first and relying on a bespoke hook to forward them onward.
+#[ext_contract(ext_multi_token)]
+trait MultiTokenCore {
+    fn mt_transfer_approved(
         &mut self,
+        owner_id: AccountId,
+        approval_id: u64,
         receiver_id: AccountId,
-        token_ids: Vec<TokenId>,
-        amounts: Vec<U128>,
-        change_amounts: Vec<U128>,
-    ) -> Promise;
+        token_id: TokenId,
+        amount: U128,
+        memo: Option<String>,
+    );
 }
 
 #[near_bindgen]
@@ -26,7 +24,9 @@ pub trait EscrowTransfer {
 #[borsh(crate = "near_sdk::borsh")]
 pub struct DeFi {
     multi_token_account_id: AccountId,
-    // this could also contain means of bookkeeping, e.g. standing orders and amounts in escrow.
+    // This contract keeps no matching state of its own - no standing orders, no record of who it
+    // has matched with whom - so it has nothing to vouch for a third party's `receiver_id` with.
+    // Until it does, `execute_swap_leg` is an owner-only pass-through: see the doc comment there.
 }
 
 #[near_bindgen]
@@ -37,72 +37,101 @@ impl DeFi {
             multi_token_account_id,
         }
     }
-}
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
-enum ExchangeAction {
-    Swap {
-        token_ids: Vec<TokenId>,
-        amounts: Vec<U128>,
-    },
-
-    // NOTE: In real world this should be calculated by the exchange, but the purpose of this
-    // exercise is to demonstrate the back-and-forth only.
-    SwapWithChange {
-        token_ids: Vec<TokenId>,
-        amounts: Vec<U128>,
-        change_amounts: Vec<U128>,
-    },
 
-    // Trigger panic to cover the test case,
-    Fail,
-}
-
-#[near_bindgen]
-impl MultiTokenReceiver for DeFi {
-    fn mt_on_transfer(
+    /// Executes one leg of a swap: pulls `amount` of `token_id` straight out of `owner_id`'s
+    /// balance and sends it to `receiver_id`, authorized by a `mt_approve` the owner granted this
+    /// contract ahead of time under `approval_id`. No prior `mt_transfer_call` push is needed.
+    ///
+    /// Note this is currently an owner-only pass-through, not the third-party pull the approval
+    /// plumbing above is shaped for: only `owner_id` itself may direct where its own allowance
+    /// goes, which an owner could equally achieve with a plain `mt_transfer` of its own.
+    /// Letting a non-owner (e.g. a matching engine) trigger this on the owner's behalf requires
+    /// `DeFi` to hold matching state of its own to vouch for the `receiver_id` it's directing
+    /// funds to - it holds none today. A two-leg swap still settles correctly in the meantime, it
+    /// just needs each owner to call their own leg (see the test below), rather than a third
+    /// party driving both.
+    pub fn execute_swap_leg(
         &mut self,
-        sender_id: AccountId,
-        token_ids: Vec<TokenId>,
-        amounts: Vec<U128>,
-        msg: String,
-    ) -> PromiseOrValue<Vec<U128>> {
-        // Verify caller.
+        owner_id: AccountId,
+        approval_id: u64,
+        token_id: TokenId,
+        amount: U128,
+        receiver_id: AccountId,
+    ) {
         require!(
-            env::predecessor_account_id() == self.multi_token_account_id,
-            "Invalid caller"
+            env::predecessor_account_id() == owner_id,
+            "Unauthorized: only the approval's owner can direct this swap leg"
+        );
+        ext_multi_token::ext(self.multi_token_account_id.clone()).mt_transfer_approved(
+            owner_id,
+            approval_id,
+            receiver_id,
+            token_id,
+            amount,
+            None,
         );
+    }
+}
 
-        let action: ExchangeAction = near_sdk::serde_json::from_str(&msg).expect("invalid message");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
 
-        match action {
-            ExchangeAction::Swap { amounts, token_ids } => {
-                ext_defi_escrow_transfer::ext(self.multi_token_account_id.clone())
-                    .escrow_transfer(
-                        sender_id,
-                        token_ids,
-                        amounts.clone(),
-                        vec![0.into(); amounts.len()],
-                    )
-                    .into()
-            }
+    fn setup_defi() -> DeFi {
+        let context = VMContextBuilder::new()
+            .predecessor_account_id(accounts(0))
+            .build();
+        testing_env!(context);
+        DeFi::new(accounts(1))
+    }
 
-            ExchangeAction::SwapWithChange {
-                token_ids,
-                amounts,
-                change_amounts,
-            } => {
-                require!(
-                    amounts.len() == change_amounts.len(),
-                    "invalid change amounts supplied"
-                );
+    #[test]
+    fn execute_swap_leg_allows_the_approval_owner() {
+        let mut defi = setup_defi();
+        let owner_id = accounts(2);
 
-                ext_defi_escrow_transfer::ext(self.multi_token_account_id.clone())
-                    .escrow_transfer(sender_id, token_ids, amounts, change_amounts)
-                    .into()
-            }
-            // Just a random failure error, abort.
-            ExchangeAction::Fail => env::panic_str("on_transfer error"),
-        }
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(owner_id.clone())
+            .build());
+        defi.execute_swap_leg(owner_id, 0, "token-1".to_string(), 10.into(), accounts(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn execute_swap_leg_rejects_a_third_party() {
+        let mut defi = setup_defi();
+        let owner_id = accounts(2);
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(3))
+            .build());
+        defi.execute_swap_leg(owner_id, 0, "token-1".to_string(), 10.into(), accounts(3));
+    }
+
+    /// A full swap is two legs, each owner directing their own approved allowance to the other -
+    /// there's no third party driving both, since `DeFi` has no matching state to vouch for one.
+    #[test]
+    fn execute_swap_leg_settles_both_legs_of_a_swap() {
+        let mut defi = setup_defi();
+        let party_a = accounts(2);
+        let party_b = accounts(3);
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(party_a.clone())
+            .build());
+        defi.execute_swap_leg(
+            party_a.clone(),
+            0,
+            "token-1".to_string(),
+            10.into(),
+            party_b.clone(),
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(party_b.clone())
+            .build());
+        defi.execute_swap_leg(party_b, 0, "token-2".to_string(), 20.into(), party_a);
     }
 }
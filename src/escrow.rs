@@ -0,0 +1,69 @@
+use crate::token::TokenId;
+use crate::Balance;
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+use std::collections::BTreeMap;
+
+/// A condition gating a [`Plan`] leaf, modeled on the Solana Budget DSL's `Condition`.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[cfg_attr(feature = "abi", derive(schemars::JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub enum Condition {
+    /// Satisfied once the block timestamp (nanoseconds) reaches or passes this value.
+    Timestamp(u64),
+    /// Satisfied once this account calls `apply_witness` on the plan.
+    Witness(AccountId),
+}
+
+/// A conditional settlement plan, modeled on the Solana Budget DSL's `Budget`/`Payment`: tokens
+/// are locked up front and released to a `Pay` leaf's receiver once the conditions guarding it are
+/// met. `After` gates a single path behind one condition; `Or` offers two alternative paths, of
+/// which at most one may ever fire (e.g. pay the recipient after a deadline, else refund the
+/// sender on a witness).
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[cfg_attr(feature = "abi", derive(schemars::JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub enum Plan {
+    /// Pays `amounts` of `token_ids` to `receiver`, unconditionally once reached.
+    Pay {
+        receiver: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+    },
+    /// Reduces to `plan` once the condition is satisfied.
+    After(Condition, Box<Plan>),
+    /// Reduces to whichever of the two `(Condition, Plan)` branches is satisfied first.
+    Or(Box<(Condition, Plan)>, Box<(Condition, Plan)>),
+}
+
+/// A stored, in-flight escrow: the plan, the account whose balance funded it, and per-token how
+/// much of that funding is actually locked up (see [`ConditionalEscrow::create_escrow`]).
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct EscrowEntry {
+    pub sender_id: AccountId,
+    pub plan: Plan,
+    pub locked: BTreeMap<TokenId, Balance>,
+}
+
+/// Conditional/escrow settlement on top of MT balances: tokens are locked into a [`Plan`] up
+/// front, then released once the plan's conditions are met.
+pub trait ConditionalEscrow {
+    /// Locks the caller's tokens into a new escrow and returns its id. Since at most one branch
+    /// of an `Or` can ever fire, the locked amount for a given token is the *maximum* reachable
+    /// across branches rather than their sum; once a branch actually fires, any of its locked
+    /// tokens the winning `Pay` leaf didn't use are refunded back to the caller.
+    fn create_escrow(&mut self, plan: Plan) -> u64;
+
+    /// Evaluates the escrow identified by `plan_id`, executing the first `Pay` leaf whose guarding
+    /// conditions are now satisfied. Callable by a leaf's `Witness` account, or by anyone once a
+    /// leaf's `Timestamp` has passed. Panics if no leaf is satisfied yet.
+    fn apply_witness(&mut self, plan_id: u64);
+
+    /// Returns the plan still pending under `plan_id`, if any (`None` once it has executed).
+    fn get_escrow(&self, plan_id: u64) -> Option<Plan>;
+}
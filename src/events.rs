@@ -0,0 +1,149 @@
+use crate::token::TokenId;
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, AccountId};
+
+/// NEP-297 standard name for multi-token events, following the `FtMint`/`FtTransfer`/`FtBurn`
+/// pattern used by the fungible-token standard.
+const STANDARD: &str = "nep245";
+const VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtMintData<'a> {
+    pub owner_id: &'a AccountId,
+    pub token_ids: &'a [TokenId],
+    pub amounts: &'a [U128],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtTransferData<'a> {
+    pub old_owner_id: &'a AccountId,
+    pub new_owner_id: &'a AccountId,
+    pub token_ids: &'a [TokenId],
+    pub amounts: &'a [U128],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtBurnData<'a> {
+    pub owner_id: &'a AccountId,
+    pub token_ids: &'a [TokenId],
+    pub amounts: &'a [U128],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtHtlcLockData<'a> {
+    pub swap_id: u64,
+    pub sender_id: &'a AccountId,
+    pub receiver_id: &'a AccountId,
+    pub token_ids: &'a [TokenId],
+    pub amounts: &'a [U128],
+    pub expiry: u64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtHtlcClaimData<'a> {
+    pub swap_id: u64,
+    pub receiver_id: &'a AccountId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtHtlcRefundData<'a> {
+    pub swap_id: u64,
+    pub sender_id: &'a AccountId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+enum EventKind<'a> {
+    MtMint(&'a [MtMintData<'a>]),
+    MtTransfer(&'a [MtTransferData<'a>]),
+    MtBurn(&'a [MtBurnData<'a>]),
+    MtHtlcLock(&'a [MtHtlcLockData<'a>]),
+    MtHtlcClaim(&'a [MtHtlcClaimData<'a>]),
+    MtHtlcRefund(&'a [MtHtlcRefundData<'a>]),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct NearEvent<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    kind: EventKind<'a>,
+}
+
+impl<'a> NearEvent<'a> {
+    // Indexers key off the `EVENT_JSON:` prefix, same as the FT/NFT standards.
+    fn emit(&self) {
+        let json = near_sdk::serde_json::to_string(self).expect("failed to serialize event");
+        env::log_str(&format!("EVENT_JSON:{json}"));
+    }
+}
+
+pub fn emit_mint(data: &[MtMintData]) {
+    NearEvent {
+        standard: STANDARD,
+        version: VERSION,
+        kind: EventKind::MtMint(data),
+    }
+    .emit();
+}
+
+pub fn emit_transfer(data: &[MtTransferData]) {
+    NearEvent {
+        standard: STANDARD,
+        version: VERSION,
+        kind: EventKind::MtTransfer(data),
+    }
+    .emit();
+}
+
+pub fn emit_burn(data: &[MtBurnData]) {
+    NearEvent {
+        standard: STANDARD,
+        version: VERSION,
+        kind: EventKind::MtBurn(data),
+    }
+    .emit();
+}
+
+pub fn emit_htlc_lock(data: &[MtHtlcLockData]) {
+    NearEvent {
+        standard: STANDARD,
+        version: VERSION,
+        kind: EventKind::MtHtlcLock(data),
+    }
+    .emit();
+}
+
+pub fn emit_htlc_claim(data: &[MtHtlcClaimData]) {
+    NearEvent {
+        standard: STANDARD,
+        version: VERSION,
+        kind: EventKind::MtHtlcClaim(data),
+    }
+    .emit();
+}
+
+pub fn emit_htlc_refund(data: &[MtHtlcRefundData]) {
+    NearEvent {
+        standard: STANDARD,
+        version: VERSION,
+        kind: EventKind::MtHtlcRefund(data),
+    }
+    .emit();
+}
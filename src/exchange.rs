@@ -0,0 +1,110 @@
+use crate::token::TokenId;
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+pub type OrderId = u64;
+
+/// A resting or partially-filled limit order: give up to `give_remaining` of `give_token_id` for
+/// at least a proportional share of `want_remaining` of `want_token_id`, at the price implied by
+/// the ratio between the two. A fill settles at the resting order's price and rounds the amount
+/// received down, so the ratio of the remaining amounts can drift from the one the order was
+/// placed at.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[cfg_attr(feature = "abi", derive(schemars::JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct Order {
+    pub id: OrderId,
+    pub owner_id: AccountId,
+    pub give_token_id: TokenId,
+    pub want_token_id: TokenId,
+    pub give_remaining: U128,
+    pub want_remaining: U128,
+}
+
+/// A rational `want_amount / give_amount` price, kept as a reduced fraction instead of a float to
+/// avoid precision loss. Orders are compared by cross-multiplication (`a/b <=> c/d` iff
+/// `a*d <=> c*b`), never by computing the quotient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct PricePoint {
+    pub numerator: u128,
+    pub denominator: u128,
+}
+
+impl PricePoint {
+    /// Builds the reduced `numerator / denominator` fraction for an order wanting `numerator` in
+    /// exchange for `denominator`.
+    pub fn new(numerator: u128, denominator: u128) -> Self {
+        let gcd = gcd(numerator, denominator).max(1);
+        Self {
+            numerator: numerator / gcd,
+            denominator: denominator / gcd,
+        }
+    }
+}
+
+impl PartialOrd for PricePoint {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PricePoint {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // a/b <=> c/d, cross-multiplied: a*d <=> c*b. All inputs are token amounts, so this can in
+        // principle overflow u128 for astronomically large orders; left unchecked, as elsewhere in
+        // this MVP.
+        (self.numerator * other.denominator).cmp(&(other.numerator * self.denominator))
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// An on-chain limit-order exchange for swapping MT balances, inspired by order-book DEXes like
+/// Orderly: placing an order locks the offered balance into the contract's custody and crosses it
+/// against the best resting opposing orders before resting any remainder on the book.
+pub trait Exchange {
+    /// Places a limit order offering `give_amount` of `give_token_id` for `want_amount` of
+    /// `want_token_id`, locking `give_amount` from the caller's balance immediately. Matches
+    /// against the best-priced resting orders on the opposite side of this pair, filling
+    /// partially if need be, then rests any unfilled remainder on the book. Returns the new
+    /// order's id.
+    fn place_order(
+        &mut self,
+        give_token_id: TokenId,
+        give_amount: U128,
+        want_token_id: TokenId,
+        want_amount: U128,
+    ) -> OrderId;
+
+    /// Cancels `order_id`, refunding its unfilled `give_remaining` back to its owner. Only the
+    /// owner may call this.
+    fn cancel_order(&mut self, order_id: OrderId);
+
+    /// Returns a page of `account_id`'s orders (filled, partially filled, or resting).
+    fn view_orders(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Order>;
+
+    /// Returns a page of the resting orders offering `give_token_id` for `want_token_id`, in
+    /// ascending price order (best, i.e. cheapest, first).
+    fn view_orderbook(
+        &self,
+        give_token_id: TokenId,
+        want_token_id: TokenId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Order>;
+}
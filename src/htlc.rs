@@ -0,0 +1,58 @@
+use crate::token::TokenId;
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+pub type SwapId = u64;
+
+/// A hash-timelocked escrow, following the Bitcoin/Monero atomic-swap pattern: `sender_id` locks
+/// tokens up front, `receiver_id` can claim them any time before `expiry` by revealing a secret
+/// whose SHA-256 hash matches `hashlock`, and `sender_id` can reclaim them once `expiry` has
+/// passed without a claim.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[cfg_attr(feature = "abi", derive(schemars::JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcSwap {
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    pub token_ids: Vec<TokenId>,
+    pub amounts: Vec<U128>,
+    pub hashlock: Base64VecU8,
+    /// Absolute block height after which the lock can no longer be claimed, only refunded.
+    pub expiry: u64,
+    /// Set once the swap has been settled, by either `claim` or `refund`. Enforces single use.
+    pub claimed: bool,
+}
+
+/// Trustless cross-account conditional transfers via hash-timelock contracts (HTLCs) - the
+/// mechanism behind Bitcoin/Monero atomic swaps. Tokens locked by `lock` are released to the
+/// counterparty by `claim` on revealing the preimage of a hashlock, or returned to the sender by
+/// `refund` once the timeout has passed, all without a trusted intermediary.
+pub trait AtomicSwap {
+    /// Locks `amounts` of `token_ids` from the caller into a new HTLC for `receiver_id`, released
+    /// by revealing a secret whose SHA-256 hash equals `hashlock` before `expiry` (an absolute
+    /// block height), or refunded to the caller once that height passes unclaimed. Returns the
+    /// new swap's id.
+    fn lock(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        hashlock: Base64VecU8,
+        expiry: u64,
+    ) -> SwapId;
+
+    /// Releases `swap_id`'s locked tokens to its `receiver_id`. Callable by anyone holding the
+    /// secret. Panics if `sha256(secret) != hashlock`, the swap was already settled, or
+    /// `env::block_height() >= expiry`.
+    fn claim(&mut self, swap_id: SwapId, secret: Base64VecU8);
+
+    /// Returns `swap_id`'s locked tokens to its `sender_id`. Only callable once
+    /// `env::block_height() >= expiry`; panics if the swap was already settled.
+    fn refund(&mut self, swap_id: SwapId);
+
+    /// Returns the swap stored under `swap_id`, if any.
+    fn get_swap(&self, swap_id: SwapId) -> Option<HtlcSwap>;
+}
@@ -1,19 +1,50 @@
+mod access;
+mod approval;
+mod escrow;
+mod events;
+mod exchange;
+mod htlc;
+mod metadata;
 mod receiver;
 mod resolver;
+mod storage;
 mod token;
 
+use crate::access::AccessControl;
+use crate::approval::{ext_mt_approval_receiver, Approval, MultiTokenApproval, TokenApprovals};
+use crate::escrow::{Condition, ConditionalEscrow, EscrowEntry, Plan};
+use crate::events::{
+    MtBurnData, MtHtlcClaimData, MtHtlcLockData, MtHtlcRefundData, MtMintData, MtTransferData,
+};
+use crate::exchange::{Exchange, Order, OrderId, PricePoint};
+use crate::htlc::{AtomicSwap, HtlcSwap, SwapId};
+use crate::metadata::{MtContractMetadata, MultiTokenMetadataProvider, TokenMetadata, MT_METADATA_SPEC};
 use crate::receiver::ext_mt_receiver;
-use crate::resolver::ext_mt_resolver;
+use crate::resolver::{ext_mt_resolver, MultiTokenResolver};
+use crate::storage::{
+    StorageBalance, StorageBalanceBounds, StorageManagement, ACCOUNT_ID_MAX_LENGTH,
+    STORAGE_PRICE_PER_BYTE,
+};
 use crate::token::{Token, TokenId};
 use crate::KeyPrefix::TokensPerOwner;
 use near_sdk::borsh::BorshSerialize;
-use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
-use near_sdk::json_types::U128;
-use near_sdk::{env, AccountId, AccountIdRef, BorshStorageKey};
-use near_sdk::{require, Gas, PromiseOrValue};
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, UnorderedSet};
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::{
+    env, near_bindgen, AccountId, AccountIdRef, BorshStorageKey, PromiseResult, StorageUsage,
+};
+use near_sdk::{require, Gas, Promise, PromiseOrValue};
 
 pub type Balance = u128;
 
+// Gas budgeted for the cross-contract `mt_on_transfer` call made by `mt_transfer_call` and its
+// batch/approved variants, and for this contract's own `mt_resolve_transfer` callback afterwards.
+// Sized generously for receivers that fan out into further cross-contract calls of their own, and
+// for resolving large `token_ids` batches; integrators attaching their own gas on top of a call
+// should budget for at least `GAS_FOR_MT_TRANSFER_CALL + GAS_FOR_MT_RESOLVE_TRANSFER`.
+const GAS_FOR_MT_TRANSFER_CALL: Gas = Gas(30_000_000_000_000);
+const GAS_FOR_MT_RESOLVE_TRANSFER: Gas = Gas(10_000_000_000_000);
+
 #[derive(BorshStorageKey, BorshSerialize)]
 #[borsh(crate = "near_sdk::borsh")]
 pub enum KeyPrefix {
@@ -24,6 +55,19 @@ pub enum KeyPrefix {
     OwnerByTokenId,
     TokensPerOwner,
     OwnerTokens,
+    AccountsStorage,
+    Approvals,
+    TokenMetadata,
+    Minters,
+    FrozenTokens,
+    Escrows,
+    Orders,
+    OrderBooks,
+    OrderBook { pair_key: Vec<u8> },
+    OrdersPerOwner,
+    OrderIdsForOwner { owner_id: Vec<u8> },
+    Htlcs,
+    AccountRefs,
 }
 
 pub struct MultiTokenContract {
@@ -35,22 +79,93 @@ pub struct MultiTokenContract {
     pub balances_per_token: UnorderedMap<TokenId, LookupMap<AccountId, u128>>,
     pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
     pub next_token_id: u64,
+    // NEP-145 storage deposits, keyed by account. Presence of a key is what "registered" means;
+    // the value is the deposited balance still held by the contract on the account's behalf.
+    pub accounts_storage: LookupMap<AccountId, Balance>,
+    // Bytes one `accounts_storage` entry costs, measured once in `new` against a worst-case
+    // (max-length) account id, rather than guessed at.
+    pub account_storage_usage: StorageUsage,
+    // NEP-178 approvals, keyed by `(token_id, owner_id)`.
+    pub approvals_by_token_owner: LookupMap<(TokenId, AccountId), TokenApprovals>,
+    // NEP-148-style contract-level metadata.
+    pub metadata: MtContractMetadata,
+    // Per-token metadata, set (optionally) at mint time.
+    pub token_metadata: LookupMap<TokenId, TokenMetadata>,
+    // Set by `propose_owner`, cleared once `accept_owner` is called by this account.
+    pub proposed_owner_id: Option<AccountId>,
+    // Accounts holding the `Minter` role, i.e. allowed to call `mt_mint`. The owner is always a
+    // minter (see `new`), but the role can be granted to other accounts too.
+    pub minters: UnorderedSet<AccountId>,
+    // Global kill switch: while `true`, `mt_transfer`/`mt_transfer_call`/batch variants fail.
+    pub paused: bool,
+    // Tokens halted individually, regardless of the global pause switch.
+    pub frozen_tokens: UnorderedSet<TokenId>,
+    // Conditional-settlement plans created via `create_escrow`, keyed by plan id. A plan is
+    // removed once it executes.
+    pub escrows: LookupMap<u64, EscrowEntry>,
+    pub next_escrow_id: u64,
+    // All orders ever placed, keyed by id, regardless of whether they're still resting.
+    pub orders: LookupMap<OrderId, Order>,
+    // Resting orders, keyed by the directed `(give_token_id, want_token_id)` pair they offer, each
+    // a price-ordered book of FIFO queues so incoming orders cross the best price first.
+    pub order_books: UnorderedMap<(TokenId, TokenId), TreeMap<PricePoint, Vec<OrderId>>>,
+    // Order ids placed by each account, for `view_orders` pagination.
+    pub orders_per_owner: LookupMap<AccountId, UnorderedSet<OrderId>>,
+    pub next_order_id: u64,
+    // Hash-timelocked swaps created via `lock`, keyed by swap id. Kept around (with `claimed` set)
+    // after settling, rather than removed, so `claim`/`refund` can't be replayed against a reused
+    // id.
+    pub htlcs: LookupMap<SwapId, HtlcSwap>,
+    pub next_swap_id: u64,
+    // Reference count per account, modeled on Substrate's provider/consumer `StoredMap` pattern:
+    // bumped while an account holds a nonzero balance of some token, has granted an outstanding
+    // approval, or is party to an unsettled HTLC lock, and dropped when each of those lapses. An
+    // account with no entry here holds no references; once the count reaches zero it is reaped
+    // (see `dec_account_ref`).
+    pub account_refs: LookupMap<AccountId, u64>,
 }
 
 // Note: approvals support has been removed for simplicity. This implementation also forgoes many
 // necessary checks and optimizations as the goal is to simply verify the public API.
-// That includes storage implementation as the `StorageManagement` trait is not specific to this
-// standard.
+#[near_bindgen]
 impl MultiTokenContract {
     /// Creates a new MultiToken contract.
     ///
     /// # Arguments
     /// * `owner_id` - contract owner.
+    #[init]
     pub fn new(owner_id: AccountId) -> Self {
         let total_supply = LookupMap::new(KeyPrefix::TotalSupply);
         let balances_per_token = UnorderedMap::new(KeyPrefix::BalancesPerToken);
         let owner_by_id = UnorderedMap::new(KeyPrefix::OwnerByTokenId);
         let tokens_per_owner = LookupMap::new(TokensPerOwner);
+        let mut accounts_storage = LookupMap::new(KeyPrefix::AccountsStorage);
+        // The owner is registered for free: it's the account that pays for minting in the first
+        // place, so there's no one else to collect a storage deposit from.
+        accounts_storage.insert(&owner_id, &0);
+        // The contract itself is registered too, since `create_escrow` holds locked balances under
+        // its own account until a plan executes.
+        accounts_storage.insert(&env::current_account_id(), &0);
+
+        // Measure a worst-case entry (max-length account id) instead of guessing its byte cost.
+        let initial_storage_usage = env::storage_usage();
+        let probe_account_id: AccountId = "a".repeat(ACCOUNT_ID_MAX_LENGTH).parse().unwrap();
+        accounts_storage.insert(&probe_account_id, &0);
+        let account_storage_usage = env::storage_usage() - initial_storage_usage;
+        accounts_storage.remove(&probe_account_id);
+
+        let approvals_by_token_owner = LookupMap::new(KeyPrefix::Approvals);
+        let token_metadata = LookupMap::new(KeyPrefix::TokenMetadata);
+        let mut minters = UnorderedSet::new(KeyPrefix::Minters);
+        // The owner can always mint; the role exists to delegate minting to others on top of that.
+        minters.insert(&owner_id);
+        let frozen_tokens = UnorderedSet::new(KeyPrefix::FrozenTokens);
+        let escrows = LookupMap::new(KeyPrefix::Escrows);
+        let orders = LookupMap::new(KeyPrefix::Orders);
+        let order_books = UnorderedMap::new(KeyPrefix::OrderBooks);
+        let orders_per_owner = LookupMap::new(KeyPrefix::OrdersPerOwner);
+        let htlcs = LookupMap::new(KeyPrefix::Htlcs);
+        let account_refs = LookupMap::new(KeyPrefix::AccountRefs);
         Self {
             owner_id,
             total_supply,
@@ -58,6 +173,32 @@ impl MultiTokenContract {
             owner_by_id,
             tokens_per_owner,
             next_token_id: 0,
+            accounts_storage,
+            account_storage_usage,
+            approvals_by_token_owner,
+            metadata: MtContractMetadata {
+                spec: MT_METADATA_SPEC.to_string(),
+                name: "MT MVP".to_string(),
+                symbol: "MT".to_string(),
+                icon: None,
+                base_uri: None,
+                reference: None,
+                reference_hash: None,
+            },
+            token_metadata,
+            proposed_owner_id: None,
+            minters,
+            paused: false,
+            frozen_tokens,
+            escrows,
+            next_escrow_id: 0,
+            orders,
+            order_books,
+            orders_per_owner,
+            next_order_id: 0,
+            htlcs,
+            next_swap_id: 0,
+            account_refs,
         }
     }
 
@@ -67,15 +208,24 @@ impl MultiTokenContract {
     /// # Arguments
     /// * `token_owner_id` - owner of this token.
     /// * `supply` - total token supply.
-    pub fn mt_mint(&mut self, token_owner_id: AccountId, supply: U128) -> Token {
-        assert_eq!(
-            env::predecessor_account_id(),
-            self.owner_id,
-            "Unauthorized: {} != {}",
-            env::predecessor_account_id(),
-            self.owner_id
+    /// * `metadata` - optional NEP-148-style metadata to attach to this token.
+    ///
+    /// If `token_owner_id` isn't registered yet, the attached deposit is used to register it
+    /// (see [`StorageManagement::storage_deposit`]).
+    #[payable]
+    pub fn mt_mint(
+        &mut self,
+        token_owner_id: AccountId,
+        supply: U128,
+        metadata: Option<TokenMetadata>,
+    ) -> Token {
+        require!(
+            self.is_minter(env::predecessor_account_id()),
+            "Unauthorized: caller does not hold the Minter role"
         );
 
+        self.ensure_registered(&token_owner_id);
+
         let supply = supply.into();
 
         self.next_token_id = self
@@ -92,14 +242,27 @@ impl MultiTokenContract {
             token_id: env::sha256(token_id.as_bytes()),
         });
         new_account_balance.insert(&token_owner_id, &supply);
+        self.inc_account_ref(&token_owner_id);
 
         self.balances_per_token
             .insert(&token_id, &new_account_balance);
 
+        if let Some(metadata) = &metadata {
+            self.token_metadata.insert(&token_id, metadata);
+        }
+
+        events::emit_mint(&[MtMintData {
+            owner_id: &token_owner_id,
+            token_ids: std::slice::from_ref(&token_id),
+            amounts: &[supply.into()],
+            memo: None,
+        }]);
+
         Token {
             token_id,
             supply,
             owner_id: token_owner_id,
+            metadata,
         }
     }
 
@@ -115,6 +278,9 @@ impl MultiTokenContract {
     /// some sort of an extension, otherwise it seems to weigh down on the core API.
     /// For simplicity this could be done as a wrapper in a different trait `mt_transfer_memo` or
     /// similar.
+    ///
+    /// If `receiver_id` isn't registered yet, the attached deposit is used to register it.
+    #[payable]
     pub fn mt_transfer(
         &mut self,
         receiver_id: AccountId,
@@ -122,11 +288,13 @@ impl MultiTokenContract {
         amount: U128,
         memo: Option<String>,
     ) {
+        self.require_transferable(&token_id);
         self.internal_transfer(
             env::predecessor_account_id(),
             receiver_id,
             token_id,
             amount.into(),
+            memo,
         );
     }
 
@@ -138,6 +306,9 @@ impl MultiTokenContract {
     /// * `amount` - total amount.
     /// * `memo` - an optional memo.
     /// * `msg`: a message that will be passed to receiving contract.
+    ///
+    /// If `receiver_id` isn't registered yet, the attached deposit is used to register it.
+    #[payable]
     pub fn mt_transfer_call(
         &mut self,
         receiver_id: AccountId,
@@ -146,17 +317,17 @@ impl MultiTokenContract {
         memo: Option<String>,
         msg: String,
     ) -> PromiseOrValue<U128> {
+        self.require_transferable(&token_id);
         self.internal_transfer(
             env::predecessor_account_id(),
             receiver_id.clone(),
             token_id.clone(),
             amount.into(),
+            memo,
         );
 
-        // Note: we default to no gas for simplicity. In the actual implementation this has to be
-        // calculated.
         ext_mt_receiver::ext(receiver_id.clone())
-            .with_static_gas(Gas::default())
+            .with_static_gas(GAS_FOR_MT_TRANSFER_CALL)
             .mt_on_transfer(
                 env::predecessor_account_id(),
                 vec![token_id.clone()],
@@ -165,7 +336,7 @@ impl MultiTokenContract {
             )
             .then(
                 ext_mt_resolver::ext(env::current_account_id())
-                    .with_static_gas(Gas::default())
+                    .with_static_gas(GAS_FOR_MT_RESOLVE_TRANSFER)
                     .mt_resolve_transfer(
                         env::predecessor_account_id(),
                         receiver_id,
@@ -189,6 +360,9 @@ impl MultiTokenContract {
     /// not amazing that way and very error-prone on the client-side. I would argue that adding a
     /// little client-side complexity for the sake of correctness is a good trade-off.
     /// NOTE: In the current implementation we create identical memos for each token.
+    ///
+    /// If `receiver_id` isn't registered yet, the attached deposit is used to register it.
+    #[payable]
     pub fn mt_batch_transfer(
         &mut self,
         receiver_id: AccountId,
@@ -216,6 +390,9 @@ impl MultiTokenContract {
     /// * `amounts` - token amounts.
     /// * `memo` - an optional memo.
     /// * `msg`: a message that will be passed to receiving contract.
+    ///
+    /// If `receiver_id` isn't registered yet, the attached deposit is used to register it.
+    #[payable]
     pub fn mt_batch_transfer_call(
         &mut self,
         receiver_id: AccountId,
@@ -231,10 +408,8 @@ impl MultiTokenContract {
             memo,
         );
 
-        // Note: we default to no gas for simplicity. In the actual implementation this has to be
-        // calculated.
         ext_mt_receiver::ext(receiver_id.clone())
-            .with_static_gas(Gas::default())
+            .with_static_gas(GAS_FOR_MT_TRANSFER_CALL)
             .mt_on_transfer(
                 env::predecessor_account_id(),
                 token_ids.clone(),
@@ -243,7 +418,7 @@ impl MultiTokenContract {
             )
             .then(
                 ext_mt_resolver::ext(env::current_account_id())
-                    .with_static_gas(Gas::default())
+                    .with_static_gas(GAS_FOR_MT_RESOLVE_TRANSFER)
                     .mt_resolve_transfer(
                         env::predecessor_account_id(),
                         receiver_id,
@@ -254,6 +429,66 @@ impl MultiTokenContract {
             .into()
     }
 
+    /// Transfers a token amount from `owner_id`'s account, on its behalf, using an approval
+    /// previously granted via [`MultiTokenApproval::mt_approve`]. The attempt fails if the
+    /// caller doesn't hold an approval for at least `amount`, or if `approval_id` is stale.
+    ///
+    /// # Arguments
+    /// * `owner_id` - the account whose balance is being moved.
+    /// * `approval_id` - the id returned by `mt_approve` for the caller's approval.
+    /// * `receiver_id` - receiver account.
+    /// * `token_id` - an id of the token to be transferred.
+    /// * `amount` - total amount.
+    /// * `memo` - an optional memo.
+    #[payable]
+    pub fn mt_transfer_approved(
+        &mut self,
+        owner_id: AccountId,
+        approval_id: u64,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        self.require_transferable(&token_id);
+        self.use_approval(&owner_id, &token_id, approval_id, amount.into());
+        self.internal_transfer(owner_id, receiver_id, token_id, amount.into(), memo);
+    }
+
+    /// Same as [`Self::mt_transfer_approved`], but also calls `mt_on_transfer` on `receiver_id`,
+    /// like `mt_transfer_call`.
+    #[payable]
+    pub fn mt_transfer_call_approved(
+        &mut self,
+        owner_id: AccountId,
+        approval_id: u64,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        self.require_transferable(&token_id);
+        self.use_approval(&owner_id, &token_id, approval_id, amount.into());
+        self.internal_transfer(
+            owner_id.clone(),
+            receiver_id.clone(),
+            token_id.clone(),
+            amount.into(),
+            memo,
+        );
+
+        ext_mt_receiver::ext(receiver_id.clone())
+            .with_static_gas(GAS_FOR_MT_TRANSFER_CALL)
+            .mt_on_transfer(owner_id.clone(), vec![token_id.clone()], vec![amount], msg)
+            .then(
+                ext_mt_resolver::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_MT_RESOLVE_TRANSFER)
+                    .mt_resolve_transfer(owner_id, receiver_id, vec![token_id], vec![amount]),
+            )
+            .into()
+    }
+
     /// Returns a token.
     ///
     /// # Arguments
@@ -263,6 +498,7 @@ impl MultiTokenContract {
     pub fn mt_token(&self, token_id: TokenId) -> Option<Token> {
         self.mt_supply(token_id.clone()).map(|supply| Token {
             owner_id: AccountIdRef::new_or_panic("not stored").into(),
+            metadata: self.token_metadata.get(&token_id),
             token_id,
             supply: supply.into(),
         })
@@ -384,6 +620,7 @@ impl MultiTokenContract {
             .expect("Total supply not found by token id");
 
         Token {
+            metadata: self.token_metadata.get(&token_id),
             token_id,
             owner_id,
             supply,
@@ -397,10 +634,13 @@ impl MultiTokenContract {
         receiver_id: AccountId,
         token_id: TokenId,
         amount: Balance,
+        memo: Option<String>,
     ) {
         require!(sender_id != receiver_id, "Sender and receiver must differ");
         require!(amount > 0, "Transferred amounts must be greater than 0");
 
+        self.ensure_registered(&receiver_id);
+
         let balance = self.internal_unwrap_balance_of(&token_id, &sender_id);
 
         let new_balance = balance.checked_sub(amount).expect("not enough balance");
@@ -408,13 +648,31 @@ impl MultiTokenContract {
             .balances_per_token
             .get(&token_id)
             .expect("Token not found");
-        balances.insert(&sender_id, &new_balance);
+        // A balance entry never persists at 0: remove it outright instead, so storage doesn't
+        // accumulate dead entries as tokens churn through many accounts.
+        if new_balance == 0 {
+            balances.remove(&sender_id);
+            self.dec_account_ref(&sender_id);
+        } else {
+            balances.insert(&sender_id, &new_balance);
+        }
 
-        let receiver_balance = self
-            .internal_unwrap_balance_of(&token_id, &receiver_id)
+        let receiver_balance_before = self.internal_unwrap_balance_of(&token_id, &receiver_id);
+        if receiver_balance_before == 0 {
+            self.inc_account_ref(&receiver_id);
+        }
+        let receiver_balance = receiver_balance_before
             .checked_add(amount)
             .expect("receiver balance overflow");
         balances.insert(&receiver_id, &receiver_balance);
+
+        events::emit_transfer(&[MtTransferData {
+            old_owner_id: &sender_id,
+            new_owner_id: &receiver_id,
+            token_ids: std::slice::from_ref(&token_id),
+            amounts: &[amount.into()],
+            memo: memo.as_deref(),
+        }]);
     }
 
     // Used to get balance of specified account in specified token
@@ -425,15 +683,1175 @@ impl MultiTokenContract {
             .get(account_id)
             .unwrap_or(0)
     }
+
+    // Registers `account_id` from the currently attached deposit if it isn't registered yet.
+    // Panics if the account is unregistered and the attached deposit can't cover the minimum.
+    fn ensure_registered(&mut self, account_id: &AccountId) {
+        if self.accounts_storage.get(account_id).is_some() {
+            return;
+        }
+
+        let deposit = env::attached_deposit();
+        let min_balance = self.storage_balance_bounds().min.0;
+        require!(
+            deposit >= min_balance,
+            format!(
+                "{account_id} is not registered for storage; attach at least {min_balance} yoctoNEAR \
+                 or call storage_deposit beforehand"
+            )
+        );
+        self.accounts_storage.insert(account_id, &deposit);
+    }
+
+    // Bumps `account_id`'s reference count. Called whenever something starts depending on the
+    // account continuing to exist: a token balance going from zero to nonzero, a newly granted
+    // approval, or a freshly locked HTLC leg.
+    fn inc_account_ref(&mut self, account_id: &AccountId) {
+        let count = self.account_refs.get(account_id).unwrap_or(0) + 1;
+        self.account_refs.insert(account_id, &count);
+    }
+
+    // Drops `account_id`'s reference count by one. Once nothing references it any longer, reaps
+    // it the same way `storage_unregister` would: drops its storage registration and refunds the
+    // deposit still held for it. Never reaps the owner or the contract's own account, both of
+    // which must stay registered regardless of what they hold.
+    fn dec_account_ref(&mut self, account_id: &AccountId) {
+        let count = self.account_refs.get(account_id).unwrap_or(0).saturating_sub(1);
+        if count > 0 {
+            self.account_refs.insert(account_id, &count);
+            return;
+        }
+        self.account_refs.remove(account_id);
+
+        if *account_id == self.owner_id || *account_id == env::current_account_id() {
+            return;
+        }
+        if let Some(balance) = self.accounts_storage.get(account_id) {
+            self.accounts_storage.remove(account_id);
+            if balance > 0 {
+                Promise::new(account_id.clone()).transfer(balance);
+            }
+        }
+    }
+
+    // Guards entry points that newly move or lock tokens - the public transfer methods, plus
+    // `create_escrow`/`place_order`/`lock` - against the global pause switch and a per-token
+    // freeze. Deliberately not enforced inside `internal_transfer` itself: system-internal
+    // settlement (escrow/HTLC/exchange unwinding) and, especially, `mt_resolve_transfer`'s
+    // refund still need to go through even while transfers are halted - an incident-response
+    // pause should stop new locks and transfers, not strand a refund mid-flight and let the
+    // receiver keep tokens it never used.
+    fn require_transferable(&self, token_id: &TokenId) {
+        require!(!self.paused, "Transfers are currently paused");
+        require!(
+            !self.is_token_frozen(token_id.clone()),
+            "This token is currently frozen"
+        );
+    }
+
+    // Validates that the predecessor holds an approval from `owner_id` on `token_id` covering
+    // `amount` under `approval_id`, and consumes that much of the allowance.
+    fn use_approval(
+        &mut self,
+        owner_id: &AccountId,
+        token_id: &TokenId,
+        approval_id: u64,
+        amount: Balance,
+    ) {
+        let key = (token_id.clone(), owner_id.clone());
+        let mut token_approvals = self
+            .approvals_by_token_owner
+            .get(&key)
+            .expect("no approvals granted for this token by this owner");
+
+        let spender = env::predecessor_account_id();
+        let approval = token_approvals
+            .by_account
+            .get_mut(&spender)
+            .expect("caller is not an approved operator for this token");
+
+        require!(approval.approval_id == approval_id, "approval_id is stale or invalid");
+        require!(approval.amount >= amount, "amount exceeds the approved allowance");
+
+        approval.amount -= amount;
+        if approval.amount == 0 {
+            token_approvals.by_account.remove(&spender);
+        }
+
+        if token_approvals.by_account.is_empty() {
+            self.approvals_by_token_owner.remove(&key);
+            self.dec_account_ref(owner_id);
+        } else {
+            self.approvals_by_token_owner.insert(&key, &token_approvals);
+        }
+    }
+
+    // Fetches the resting-order book offering `give_token_id` for `want_token_id`, creating an
+    // empty one (with a deterministic storage prefix derived from the pair) if none exists yet.
+    fn get_book(
+        &self,
+        give_token_id: &TokenId,
+        want_token_id: &TokenId,
+    ) -> TreeMap<PricePoint, Vec<OrderId>> {
+        let pair = (give_token_id.clone(), want_token_id.clone());
+        self.order_books.get(&pair).unwrap_or_else(|| {
+            let pair_key = env::sha256(format!("{give_token_id}\0{want_token_id}").as_bytes());
+            TreeMap::new(KeyPrefix::OrderBook { pair_key })
+        })
+    }
+
+    // Rests `order` on its own `(give_token_id, want_token_id)` book, at the price implied by its
+    // current remaining amounts.
+    fn rest_order(&mut self, order: &Order) {
+        let pair = (order.give_token_id.clone(), order.want_token_id.clone());
+        let price = PricePoint::new(order.want_remaining.0, order.give_remaining.0);
+
+        let mut book = self.get_book(&order.give_token_id, &order.want_token_id);
+        let mut queue = book.get(&price).unwrap_or_default();
+        queue.push(order.id);
+        book.insert(&price, &queue);
+        self.order_books.insert(&pair, &book);
+    }
+
+    // Removes `order` from its resting book, if it's on one. Used by `cancel_order`. The order is
+    // found by id rather than by the price its remaining amounts reduce to now, since a partial
+    // fill can shift that ratio away from the price it rests under.
+    fn remove_from_book(&mut self, order: &Order) {
+        let pair = (order.give_token_id.clone(), order.want_token_id.clone());
+
+        let mut book = self.get_book(&order.give_token_id, &order.want_token_id);
+        let resting = book.iter().find(|(_, queue)| queue.contains(&order.id));
+        if let Some((price, mut queue)) = resting {
+            queue.retain(|id| *id != order.id);
+            if queue.is_empty() {
+                book.remove(&price);
+            } else {
+                book.insert(&price, &queue);
+            }
+        }
+        self.order_books.insert(&pair, &book);
+    }
+
+    // Crosses `taker` against the best-priced resting orders on the opposite side of its pair,
+    // filling partially and settling each fill atomically, until either `taker` is exhausted or
+    // the best remaining opposing price no longer crosses.
+    fn match_order(&mut self, taker: &mut Order) {
+        let contract_id = env::current_account_id();
+
+        loop {
+            if taker.give_remaining.0 == 0 {
+                break;
+            }
+
+            let mut book = self.get_book(&taker.want_token_id, &taker.give_token_id);
+            let Some((price, mut queue)) = book.iter().next() else {
+                break;
+            };
+            let Some(maker_id) = queue.first().copied() else {
+                book.remove(&price);
+                self.order_books
+                    .insert(&(taker.want_token_id.clone(), taker.give_token_id.clone()), &book);
+                continue;
+            };
+            let mut maker = self.orders.get(&maker_id).expect("dangling order id in book");
+
+            if !prices_cross(taker, &maker) {
+                break;
+            }
+
+            // Trade size in units of `taker.give_token_id`, capped by all three limits in play:
+            // the taker's remaining give, the maker's remaining want, and - since the maker's
+            // price can be strictly better than the taker's own limit - however much the taker
+            // can still afford to want at the maker's (fixed) execution price. Without that last
+            // cap, `trade_want` could exceed `taker.want_remaining`, leaving it to saturate to 0
+            // while `give_remaining` stays positive and the leftover rests on the book offering
+            // tokens away for free.
+            let max_give_within_taker_limit = taker
+                .want_remaining
+                .0
+                .checked_mul(maker.want_remaining.0)
+                .expect("trade amount overflow")
+                / maker.give_remaining.0;
+            let trade_give = std::cmp::min(
+                std::cmp::min(taker.give_remaining.0, maker.want_remaining.0),
+                max_give_within_taker_limit,
+            );
+            let trade_want = trade_give
+                .checked_mul(maker.give_remaining.0)
+                .expect("trade amount overflow")
+                / maker.want_remaining.0;
+            if trade_give == 0 || trade_want == 0 {
+                break;
+            }
+
+            // Settle atomically: swap the two locked balances directly between the owners.
+            self.internal_transfer(
+                contract_id.clone(),
+                maker.owner_id.clone(),
+                taker.give_token_id.clone(),
+                trade_give,
+                None,
+            );
+            self.internal_transfer(
+                contract_id.clone(),
+                taker.owner_id.clone(),
+                maker.give_token_id.clone(),
+                trade_want,
+                None,
+            );
+
+            taker.give_remaining = U128(taker.give_remaining.0 - trade_give);
+            taker.want_remaining = U128(taker.want_remaining.0.saturating_sub(trade_want));
+            maker.give_remaining = U128(maker.give_remaining.0 - trade_want);
+            maker.want_remaining = U128(maker.want_remaining.0 - trade_give);
+
+            if maker.give_remaining.0 == 0 {
+                queue.remove(0);
+                if queue.is_empty() {
+                    book.remove(&price);
+                } else {
+                    book.insert(&price, &queue);
+                }
+                self.order_books
+                    .insert(&(taker.want_token_id.clone(), taker.give_token_id.clone()), &book);
+                // The maker's order no longer rests on the book, so it no longer keeps the
+                // maker registered.
+                self.dec_account_ref(&maker.owner_id);
+            }
+
+            self.orders.insert(&maker_id, &maker);
+
+            // At the maker's better price the taker can get all it wanted while some of its give
+            // is still left; hand that back rather than resting it on the book for nothing.
+            if taker.want_remaining.0 == 0 && taker.give_remaining.0 > 0 {
+                self.internal_transfer(
+                    contract_id.clone(),
+                    taker.owner_id.clone(),
+                    taker.give_token_id.clone(),
+                    taker.give_remaining.0,
+                    None,
+                );
+                taker.give_remaining = U128(0);
+            }
+        }
+    }
+}
+
+// Whether `taker`'s asking price crosses `maker`'s offered price, i.e. whether `maker` offers at
+// least as much of `taker`'s `want` asset per unit of `taker`'s `give` asset as `taker` asks for.
+// Cross-multiplied to stay in integer arithmetic: `taker` gives `ga` wanting `wa`, `maker` gives
+// `gb` (== taker's wanted asset) wanting `wb` (== taker's given asset); they cross iff
+// `wa/ga <= gb/wb`, i.e. `wa*wb <= ga*gb`.
+fn prices_cross(taker: &Order, maker: &Order) -> bool {
+    let ga = taker.give_remaining.0;
+    let wa = taker.want_remaining.0;
+    let gb = maker.give_remaining.0;
+    let wb = maker.want_remaining.0;
+
+    match (wa.checked_mul(wb), ga.checked_mul(gb)) {
+        (Some(lhs), Some(rhs)) => lhs <= rhs,
+        _ => false,
+    }
+}
+
+#[near_bindgen]
+impl AccessControl for MultiTokenContract {
+    fn propose_owner(&mut self, new_owner_id: AccountId) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "Unauthorized: caller is not the owner"
+        );
+        self.proposed_owner_id = Some(new_owner_id);
+    }
+
+    fn accept_owner(&mut self) {
+        let caller = env::predecessor_account_id();
+        require!(
+            self.proposed_owner_id.as_ref() == Some(&caller),
+            "Unauthorized: caller is not the proposed owner"
+        );
+        self.owner_id = caller;
+        self.proposed_owner_id = None;
+    }
+
+    fn add_minter(&mut self, account_id: AccountId) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "Unauthorized: caller is not the owner"
+        );
+        self.minters.insert(&account_id);
+    }
+
+    fn remove_minter(&mut self, account_id: AccountId) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "Unauthorized: caller is not the owner"
+        );
+        self.minters.remove(&account_id);
+    }
+
+    fn is_minter(&self, account_id: AccountId) -> bool {
+        self.minters.contains(&account_id)
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "Unauthorized: caller is not the owner"
+        );
+        self.paused = paused;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn freeze_token(&mut self, token_id: TokenId) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "Unauthorized: caller is not the owner"
+        );
+        self.frozen_tokens.insert(&token_id);
+    }
+
+    fn unfreeze_token(&mut self, token_id: TokenId) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "Unauthorized: caller is not the owner"
+        );
+        self.frozen_tokens.remove(&token_id);
+    }
+
+    fn is_token_frozen(&self, token_id: TokenId) -> bool {
+        self.frozen_tokens.contains(&token_id)
+    }
+}
+
+#[near_bindgen]
+impl ConditionalEscrow for MultiTokenContract {
+    fn create_escrow(&mut self, plan: Plan) -> u64 {
+        let sender_id = env::predecessor_account_id();
+
+        let mut locked: std::collections::BTreeMap<TokenId, Balance> =
+            std::collections::BTreeMap::new();
+        collect_plan_amounts(&plan, &mut locked);
+        require!(!locked.is_empty(), "plan has no Pay leaves to fund");
+        for token_id in locked.keys() {
+            self.require_transferable(token_id);
+        }
+
+        let contract_id = env::current_account_id();
+        // Bumped before the transfer loop below, so that funding a plan with one's entire
+        // balance doesn't transiently hit a zero reference count and get reaped mid-call, only
+        // to need re-registering the moment `apply_witness` pays back out to a `sender_id` leg.
+        self.inc_account_ref(&sender_id);
+        for (token_id, amount) in &locked {
+            self.internal_transfer(
+                sender_id.clone(),
+                contract_id.clone(),
+                token_id.clone(),
+                *amount,
+                None,
+            );
+        }
+
+        self.next_escrow_id = self
+            .next_escrow_id
+            .checked_add(1)
+            .expect("escrow id overflow, can't create any more escrows");
+        let plan_id = self.next_escrow_id;
+        self.escrows.insert(
+            &plan_id,
+            &EscrowEntry {
+                sender_id,
+                plan,
+                locked,
+            },
+        );
+        plan_id
+    }
+
+    fn apply_witness(&mut self, plan_id: u64) {
+        let entry = self.escrows.get(&plan_id).expect("no such escrow");
+        let caller = env::predecessor_account_id();
+
+        let pay = resolve_plan(&entry.plan, &caller)
+            .expect("plan conditions are not yet satisfied")
+            .clone();
+
+        let Plan::Pay {
+            receiver,
+            token_ids,
+            amounts,
+        } = pay
+        else {
+            unreachable!("resolve_plan only ever returns a Pay leaf");
+        };
+
+        let contract_id = env::current_account_id();
+        let mut leftover = entry.locked.clone();
+        for (token_id, amount) in token_ids.into_iter().zip(amounts) {
+            self.internal_transfer(
+                contract_id.clone(),
+                receiver.clone(),
+                token_id.clone(),
+                amount.into(),
+                None,
+            );
+            if let Some(locked_amount) = leftover.get_mut(&token_id) {
+                *locked_amount = locked_amount
+                    .checked_sub(amount.0)
+                    .expect("winning branch paid out more than was locked for it");
+            }
+        }
+
+        // The branch that fired may need less of some token than the losing branch would have -
+        // `create_escrow` locked the max of the two so either could be paid in full. Refund
+        // whatever's left over rather than stranding it in the contract.
+        for (token_id, amount) in leftover {
+            if amount > 0 {
+                self.internal_transfer(
+                    contract_id.clone(),
+                    entry.sender_id.clone(),
+                    token_id,
+                    amount,
+                    None,
+                );
+            }
+        }
+
+        // A plan executes at most once: drop it as soon as one branch has fired.
+        self.escrows.remove(&plan_id);
+        // The plan is settled now, so it no longer keeps its funder registered on its own.
+        self.dec_account_ref(&entry.sender_id);
+    }
+
+    fn get_escrow(&self, plan_id: u64) -> Option<Plan> {
+        self.escrows.get(&plan_id).map(|entry| entry.plan)
+    }
+}
+
+#[near_bindgen]
+impl Exchange for MultiTokenContract {
+    fn place_order(
+        &mut self,
+        give_token_id: TokenId,
+        give_amount: U128,
+        want_token_id: TokenId,
+        want_amount: U128,
+    ) -> OrderId {
+        require!(
+            give_token_id != want_token_id,
+            "give_token_id and want_token_id must differ"
+        );
+        require!(
+            give_amount.0 > 0 && want_amount.0 > 0,
+            "give_amount and want_amount must be greater than 0"
+        );
+        self.require_transferable(&give_token_id);
+        self.require_transferable(&want_token_id);
+
+        let owner_id = env::predecessor_account_id();
+        let contract_id = env::current_account_id();
+
+        // Bumped before the lock below, mirroring escrow/HTLC: offering one's entire balance
+        // into a resting order shouldn't transiently reap the account mid-call. Released once
+        // the order no longer rests on the book - immediately below if it fills in full here, or
+        // later from `cancel_order`/`match_order` once it does.
+        self.inc_account_ref(&owner_id);
+
+        // Lock the offered balance into the contract's own custody up front.
+        self.internal_transfer(
+            owner_id.clone(),
+            contract_id,
+            give_token_id.clone(),
+            give_amount.0,
+            None,
+        );
+
+        self.next_order_id = self
+            .next_order_id
+            .checked_add(1)
+            .expect("order id overflow, can't place any more orders");
+        let order_id = self.next_order_id;
+
+        let mut order = Order {
+            id: order_id,
+            owner_id: owner_id.clone(),
+            give_token_id,
+            want_token_id,
+            give_remaining: give_amount,
+            want_remaining: want_amount,
+        };
+
+        self.match_order(&mut order);
+
+        if order.give_remaining.0 > 0 {
+            self.rest_order(&order);
+        } else {
+            // Filled in full already - no resting order is left to protect.
+            self.dec_account_ref(&owner_id);
+        }
+
+        self.orders.insert(&order_id, &order);
+
+        let mut owner_orders = self.orders_per_owner.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(KeyPrefix::OrderIdsForOwner {
+                owner_id: env::sha256(owner_id.as_bytes()),
+            })
+        });
+        owner_orders.insert(&order_id);
+        self.orders_per_owner.insert(&owner_id, &owner_orders);
+
+        order_id
+    }
+
+    fn cancel_order(&mut self, order_id: OrderId) {
+        let mut order = self.orders.get(&order_id).expect("no such order");
+        require!(
+            env::predecessor_account_id() == order.owner_id,
+            "Unauthorized: caller does not own this order"
+        );
+        require!(
+            order.give_remaining.0 > 0,
+            "order has no unfilled remainder to cancel"
+        );
+
+        self.remove_from_book(&order);
+
+        let contract_id = env::current_account_id();
+        self.internal_transfer(
+            contract_id,
+            order.owner_id.clone(),
+            order.give_token_id.clone(),
+            order.give_remaining.0,
+            None,
+        );
+
+        order.give_remaining = U128(0);
+        self.orders.insert(&order_id, &order);
+        // The order no longer rests on the book, so it no longer keeps its owner registered.
+        self.dec_account_ref(&order.owner_id);
+    }
+
+    fn view_orders(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Order> {
+        self.orders_per_owner
+            .get(&account_id)
+            .map(|set| {
+                set.iter()
+                    .skip(from_index.unwrap_or_default().0 as usize)
+                    .take(limit.unwrap_or(u64::MAX) as usize)
+                    .map(|order_id| self.orders.get(&order_id).expect("dangling order id"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn view_orderbook(
+        &self,
+        give_token_id: TokenId,
+        want_token_id: TokenId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Order> {
+        let book = self.get_book(&give_token_id, &want_token_id);
+        book.iter()
+            .flat_map(|(_, queue)| queue)
+            .skip(from_index.unwrap_or_default().0 as usize)
+            .take(limit.unwrap_or(u64::MAX) as usize)
+            .map(|order_id| self.orders.get(&order_id).expect("dangling order id"))
+            .collect()
+    }
+}
+
+#[near_bindgen]
+impl AtomicSwap for MultiTokenContract {
+    fn lock(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        hashlock: Base64VecU8,
+        expiry: u64,
+    ) -> SwapId {
+        require!(
+            token_ids.len() == amounts.len(),
+            "each token must have its corresponding amount and vice versa"
+        );
+        require!(!token_ids.is_empty(), "must lock at least one token");
+        require!(
+            expiry > env::block_height(),
+            "expiry must be in the future"
+        );
+        for token_id in &token_ids {
+            self.require_transferable(token_id);
+        }
+
+        let sender_id = env::predecessor_account_id();
+        let contract_id = env::current_account_id();
+        // Bumped before the transfer loop below, so that a sender locking away their entire
+        // balance of every token doesn't transiently hit a zero reference count and get reaped
+        // mid-call, only to need re-registering the moment `refund`/`claim` pays them back out.
+        self.inc_account_ref(&sender_id);
+        self.inc_account_ref(&receiver_id);
+        for (token_id, amount) in token_ids.iter().zip(amounts.iter()) {
+            self.internal_transfer(
+                sender_id.clone(),
+                contract_id.clone(),
+                token_id.clone(),
+                amount.0,
+                None,
+            );
+        }
+
+        self.next_swap_id = self
+            .next_swap_id
+            .checked_add(1)
+            .expect("swap id overflow, can't lock any more swaps");
+        let swap_id = self.next_swap_id;
+
+        events::emit_htlc_lock(&[MtHtlcLockData {
+            swap_id,
+            sender_id: &sender_id,
+            receiver_id: &receiver_id,
+            token_ids: &token_ids,
+            amounts: &amounts,
+            expiry,
+        }]);
+
+        self.htlcs.insert(
+            &swap_id,
+            &HtlcSwap {
+                sender_id,
+                receiver_id,
+                token_ids,
+                amounts,
+                hashlock,
+                expiry,
+                claimed: false,
+            },
+        );
+
+        swap_id
+    }
+
+    fn claim(&mut self, swap_id: SwapId, secret: Base64VecU8) {
+        let mut swap = self.htlcs.get(&swap_id).expect("no such swap");
+        require!(!swap.claimed, "swap has already been settled");
+        require!(
+            env::block_height() < swap.expiry,
+            "swap has expired; call refund instead"
+        );
+        require!(
+            env::sha256(&secret.0) == swap.hashlock.0,
+            "secret does not match the swap's hashlock"
+        );
+
+        let contract_id = env::current_account_id();
+        for (token_id, amount) in swap.token_ids.iter().zip(swap.amounts.iter()) {
+            self.internal_transfer(
+                contract_id.clone(),
+                swap.receiver_id.clone(),
+                token_id.clone(),
+                amount.0,
+                None,
+            );
+        }
+
+        swap.claimed = true;
+        self.htlcs.insert(&swap_id, &swap);
+        // The swap is settled now, so it no longer keeps either party registered on its own.
+        self.dec_account_ref(&swap.sender_id);
+        self.dec_account_ref(&swap.receiver_id);
+
+        events::emit_htlc_claim(&[MtHtlcClaimData {
+            swap_id,
+            receiver_id: &swap.receiver_id,
+        }]);
+    }
+
+    fn refund(&mut self, swap_id: SwapId) {
+        let mut swap = self.htlcs.get(&swap_id).expect("no such swap");
+        require!(!swap.claimed, "swap has already been settled");
+        require!(
+            env::block_height() >= swap.expiry,
+            "swap has not expired yet"
+        );
+
+        let contract_id = env::current_account_id();
+        for (token_id, amount) in swap.token_ids.iter().zip(swap.amounts.iter()) {
+            self.internal_transfer(
+                contract_id.clone(),
+                swap.sender_id.clone(),
+                token_id.clone(),
+                amount.0,
+                None,
+            );
+        }
+
+        swap.claimed = true;
+        self.htlcs.insert(&swap_id, &swap);
+        // The swap is settled now, so it no longer keeps either party registered on its own.
+        self.dec_account_ref(&swap.sender_id);
+        self.dec_account_ref(&swap.receiver_id);
+
+        events::emit_htlc_refund(&[MtHtlcRefundData {
+            swap_id,
+            sender_id: &swap.sender_id,
+        }]);
+    }
+
+    fn get_swap(&self, swap_id: SwapId) -> Option<HtlcSwap> {
+        self.htlcs.get(&swap_id)
+    }
+}
+
+#[near_bindgen]
+impl MultiTokenApproval for MultiTokenContract {
+    #[payable]
+    fn mt_approve(
+        &mut self,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        require!(
+            token_ids.len() == amounts.len(),
+            "each token must have its corresponding amount and vice versa"
+        );
+
+        let owner_id = env::predecessor_account_id();
+        let mut approval_ids = Vec::with_capacity(token_ids.len());
+
+        for (token_id, amount) in token_ids.iter().zip(amounts.iter()) {
+            let balance = self.internal_unwrap_balance_of(token_id, &owner_id);
+            require!(balance >= amount.0, "cannot approve more than the current balance");
+
+            let key = (token_id.clone(), owner_id.clone());
+            let mut token_approvals = self.approvals_by_token_owner.get(&key).unwrap_or_default();
+            if token_approvals.by_account.is_empty() {
+                self.inc_account_ref(&owner_id);
+            }
+            let approval_id = token_approvals.next_approval_id;
+            token_approvals.next_approval_id += 1;
+            token_approvals.by_account.insert(
+                account_id.clone(),
+                Approval {
+                    approval_id,
+                    amount: amount.0,
+                },
+            );
+            self.approvals_by_token_owner.insert(&key, &token_approvals);
+            approval_ids.push(approval_id);
+        }
+
+        msg.map(|msg| {
+            ext_mt_approval_receiver::ext(account_id)
+                .with_static_gas(Gas::default())
+                .mt_on_approve(token_ids, owner_id, approval_ids, msg)
+        })
+    }
+
+    fn mt_revoke(&mut self, token_ids: Vec<TokenId>, account_id: AccountId) {
+        let owner_id = env::predecessor_account_id();
+        for token_id in token_ids {
+            let key = (token_id, owner_id.clone());
+            if let Some(mut token_approvals) = self.approvals_by_token_owner.get(&key) {
+                token_approvals.by_account.remove(&account_id);
+                if token_approvals.by_account.is_empty() {
+                    self.approvals_by_token_owner.remove(&key);
+                    self.dec_account_ref(&owner_id);
+                } else {
+                    self.approvals_by_token_owner.insert(&key, &token_approvals);
+                }
+            }
+        }
+    }
+
+    fn mt_revoke_all(&mut self, token_ids: Vec<TokenId>) {
+        let owner_id = env::predecessor_account_id();
+        for token_id in token_ids {
+            if self
+                .approvals_by_token_owner
+                .remove(&(token_id, owner_id.clone()))
+                .is_some()
+            {
+                self.dec_account_ref(&owner_id);
+            }
+        }
+    }
+
+    fn mt_is_approved(
+        &self,
+        owner_id: AccountId,
+        approved_account_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        approval_ids: Option<Vec<u64>>,
+    ) -> bool {
+        require!(
+            token_ids.len() == amounts.len(),
+            "each token must have its corresponding amount and vice versa"
+        );
+
+        token_ids
+            .iter()
+            .zip(amounts.iter())
+            .enumerate()
+            .all(|(i, (token_id, amount))| {
+                let key = (token_id.clone(), owner_id.clone());
+                let approval = self
+                    .approvals_by_token_owner
+                    .get(&key)
+                    .and_then(|t| t.by_account.get(&approved_account_id).copied());
+
+                match approval {
+                    Some(approval) => {
+                        approval.amount >= amount.0
+                            && approval_ids
+                                .as_ref()
+                                .map(|ids| ids.get(i) == Some(&approval.approval_id))
+                                .unwrap_or(true)
+                    }
+                    None => false,
+                }
+            })
+    }
+}
+
+#[near_bindgen]
+impl MultiTokenMetadataProvider for MultiTokenContract {
+    fn mt_metadata(&self) -> MtContractMetadata {
+        self.metadata.clone()
+    }
+
+    fn mt_token_metadata(&self, token_ids: Vec<TokenId>) -> Vec<Option<TokenMetadata>> {
+        token_ids
+            .into_iter()
+            .map(|token_id| self.token_metadata.get(&token_id))
+            .collect()
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for MultiTokenContract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let min_balance = self.storage_balance_bounds().min.0;
+
+        // NOTE: this MVP always reserves exactly `min_balance`; `registration_only` has nothing
+        // extra to opt out of, but is kept on the signature for NEP-145 compatibility.
+        let _ = registration_only;
+
+        let total = match self.accounts_storage.get(&account_id) {
+            Some(existing) => existing
+                .checked_add(amount)
+                .expect("storage balance overflow"),
+            None => {
+                require!(
+                    amount >= min_balance,
+                    format!("attached deposit must be at least {min_balance} yoctoNEAR")
+                );
+                amount
+            }
+        };
+
+        self.accounts_storage.insert(&account_id, &total);
+
+        StorageBalance {
+            total: total.into(),
+            available: (total - min_balance).into(),
+        }
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        require!(
+            env::attached_deposit() == 1,
+            "Requires attached deposit of exactly 1 yoctoNEAR"
+        );
+
+        let account_id = env::predecessor_account_id();
+        let balance = self
+            .accounts_storage
+            .get(&account_id)
+            .expect("account is not registered");
+        let min_balance = self.storage_balance_bounds().min.0;
+        let available = balance - min_balance;
+
+        let amount: Balance = amount.map(|a| a.0).unwrap_or(available);
+        require!(
+            amount <= available,
+            "amount exceeds the available storage balance"
+        );
+
+        let total = balance - amount;
+        self.accounts_storage.insert(&account_id, &total);
+
+        if amount > 0 {
+            Promise::new(account_id).transfer(amount);
+        }
+
+        StorageBalance {
+            total: total.into(),
+            available: (total - min_balance).into(),
+        }
+    }
+
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        require!(
+            env::attached_deposit() == 1,
+            "Requires attached deposit of exactly 1 yoctoNEAR"
+        );
+
+        let account_id = env::predecessor_account_id();
+        let Some(balance) = self.accounts_storage.get(&account_id) else {
+            return false;
+        };
+        let force = force.unwrap_or(false);
+
+        let held_balances: Vec<(TokenId, Balance)> = self
+            .owner_by_id
+            .keys()
+            .filter_map(|token_id| {
+                let amount = self.internal_unwrap_balance_of(&token_id, &account_id);
+                (amount > 0).then_some((token_id, amount))
+            })
+            .collect();
+
+        // `held_balances` only covers tokens this account directly holds - it doesn't see a
+        // nonzero `account_refs` kept alive by being the counterparty of a still-open
+        // HTLC/escrow/resting order, whose locked balance sits under `contract_id` instead.
+        // Unregistering out from under one of those strands it: the swap's `claim`/`refund`/
+        // `apply_witness`/`cancel_order` settle via `internal_transfer` -> `ensure_registered`,
+        // which panics without an attached deposit to re-register with. Reject the same way an
+        // unforced nonzero `held_balances` is rejected below.
+        require!(
+            self.account_refs.get(&account_id).unwrap_or(0) <= held_balances.len() as u64,
+            "account is still referenced by an open escrow, swap, order, or approval; settle, cancel, or revoke it first"
+        );
+
+        if !held_balances.is_empty() {
+            require!(
+                force,
+                "account still holds non-zero token balances; pass force=true to burn them"
+            );
+
+            for (token_id, amount) in &held_balances {
+                let mut balances = self
+                    .balances_per_token
+                    .get(token_id)
+                    .expect("Token not found");
+                balances.remove(&account_id);
+                let supply = self
+                    .total_supply
+                    .get(token_id)
+                    .expect("Token not found")
+                    .checked_sub(*amount)
+                    .expect("total supply underflow");
+                self.total_supply.insert(token_id, &supply);
+            }
+            // Not routed through `dec_account_ref`: it would reap `account_id` the moment its
+            // count hits 0, but the unregistration and refund below happen unconditionally
+            // regardless of what `account_refs` says, so doing both would refund it twice.
+            let remaining = self
+                .account_refs
+                .get(&account_id)
+                .unwrap_or(0)
+                .saturating_sub(held_balances.len() as u64);
+            if remaining > 0 {
+                self.account_refs.insert(&account_id, &remaining);
+            } else {
+                self.account_refs.remove(&account_id);
+            }
+
+            let token_ids: Vec<TokenId> =
+                held_balances.iter().map(|(id, _)| id.clone()).collect();
+            let amounts: Vec<U128> = held_balances.iter().map(|(_, a)| (*a).into()).collect();
+            events::emit_burn(&[MtBurnData {
+                owner_id: &account_id,
+                token_ids: &token_ids,
+                amounts: &amounts,
+                memo: Some("storage_unregister force burn"),
+            }]);
+        }
+
+        self.accounts_storage.remove(&account_id);
+        if balance > 0 {
+            Promise::new(account_id).transfer(balance);
+        }
+
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        let min: Balance = Balance::from(self.account_storage_usage) * STORAGE_PRICE_PER_BYTE;
+        StorageBalanceBounds {
+            min: min.into(),
+            max: Some(min.into()),
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        let min_balance = self.storage_balance_bounds().min.0;
+        self.accounts_storage.get(&account_id).map(|balance| StorageBalance {
+            total: balance.into(),
+            available: balance.saturating_sub(min_balance).into(),
+        })
+    }
+}
+
+#[near_bindgen]
+impl MultiTokenResolver for MultiTokenContract {
+    /// Finalizes a `mt_transfer_call`/`mt_batch_transfer_call` chain.
+    ///
+    /// Reads the single promise result left by `mt_on_transfer`, interprets it as the `Vec<U128>`
+    /// of amounts the receiver left unused (a failed promise means nothing was used), and refunds
+    /// `min(unused, receiver's current balance)` back to `sender_id` per token - the cap accounts
+    /// for the receiver having already spent some of what it was sent by the time we get here.
+    ///
+    /// This cap is also what happens to cover the receiver force-unregistering
+    /// (`storage_unregister(force: true)`) in between `mt_on_transfer` and this callback: that
+    /// path already burns the receiver's held balance down to 0 and emits its own burn event, so
+    /// here the cap simply refunds nothing rather than underflowing or panicking. That's a
+    /// deliberate choice, not an oversight: the unused amount is forfeited the same way the rest
+    /// of a force-unregistered account's holdings are, consistent with `storage_unregister`'s
+    /// existing burn semantics, rather than inventing a second, different recovery path (crediting
+    /// it to this contract, or redirecting it to some other still-registered account) that
+    /// `storage_unregister` itself doesn't offer for the receiver's other tokens.
+    /// Returns the amount actually spent (`sent - refunded`) for each token.
+    #[private]
+    fn mt_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+    ) -> Vec<U128> {
+        require!(
+            env::promise_results_count() == 1,
+            "Expected a single promise result from `mt_on_transfer`"
+        );
+
+        let unused_amounts: Vec<U128> = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice(&value).unwrap_or_else(|_| amounts.clone())
+            }
+            PromiseResult::Failed => amounts.clone(),
+        };
+
+        token_ids
+            .into_iter()
+            .zip(amounts)
+            .zip(unused_amounts)
+            .map(|((token_id, sent_amount), unused_amount)| {
+                let sent: Balance = sent_amount.into();
+                let unused: Balance = unused_amount.into();
+                let unused = std::cmp::min(unused, sent);
+
+                if unused == 0 {
+                    return U128(sent);
+                }
+
+                let receiver_balance = self.internal_unwrap_balance_of(&token_id, &receiver_id);
+                let refund = std::cmp::min(unused, receiver_balance);
+
+                if refund > 0 {
+                    self.internal_transfer(
+                        receiver_id.clone(),
+                        sender_id.clone(),
+                        token_id,
+                        refund,
+                        None,
+                    );
+                }
+
+                U128(sent - refund)
+            })
+            .collect()
+    }
+}
+
+// Computes, per token, how much of `plan` needs to be locked up front so that whichever path
+// fires is guaranteed to find its tokens already there. `After` recurses into its single path
+// unconditionally. `Or`'s two branches are mutually exclusive - only one will ever pay out - so
+// its locked amount per token is the *maximum* of what each branch needs, not their sum; any
+// slack left over once a branch actually fires is refunded in `apply_witness`.
+fn collect_plan_amounts(plan: &Plan, totals: &mut std::collections::BTreeMap<TokenId, Balance>) {
+    match plan {
+        Plan::Pay {
+            token_ids, amounts, ..
+        } => {
+            for (token_id, amount) in token_ids.iter().zip(amounts.iter()) {
+                *totals.entry(token_id.clone()).or_insert(0) += amount.0;
+            }
+        }
+        Plan::After(_, inner) => collect_plan_amounts(inner, totals),
+        Plan::Or(a, b) => {
+            let mut branch_a = std::collections::BTreeMap::new();
+            collect_plan_amounts(&a.1, &mut branch_a);
+            let mut branch_b = std::collections::BTreeMap::new();
+            collect_plan_amounts(&b.1, &mut branch_b);
+            for (token_id, amount) in branch_a.into_iter().chain(branch_b) {
+                let entry = totals.entry(token_id).or_insert(0);
+                *entry = (*entry).max(amount);
+            }
+        }
+    }
+}
+
+fn condition_satisfied(condition: &Condition, caller: &AccountId) -> bool {
+    match condition {
+        Condition::Timestamp(timestamp) => env::block_timestamp() >= *timestamp,
+        Condition::Witness(witness) => caller == witness,
+    }
+}
+
+// Walks down `plan` along whichever path has its conditions satisfied, returning the `Pay` leaf to
+// execute, or `None` if no path is satisfied yet.
+fn resolve_plan<'a>(plan: &'a Plan, caller: &AccountId) -> Option<&'a Plan> {
+    match plan {
+        Plan::Pay { .. } => Some(plan),
+        Plan::After(condition, inner) => {
+            condition_satisfied(condition, caller).then(|| resolve_plan(inner, caller))?
+        }
+        // A branch whose own condition holds but whose inner plan doesn't resolve yet doesn't stop
+        // the other branch from firing.
+        Plan::Or(a, b) => condition_satisfied(&a.0, caller)
+            .then(|| resolve_plan(&a.1, caller))
+            .flatten()
+            .or_else(|| {
+                condition_satisfied(&b.0, caller)
+                    .then(|| resolve_plan(&b.1, caller))
+                    .flatten()
+            }),
+    }
 }
 
 // TODO: Implement resolver/receiver to test token exchange use-cases.
 #[cfg(test)]
 mod tests {
+    use crate::escrow::{Condition, ConditionalEscrow, Plan};
+    use crate::resolver::MultiTokenResolver;
+    use crate::storage::StorageManagement;
     use crate::token::{Token, TokenId};
     use crate::{Balance, MultiTokenContract};
+    use near_sdk::json_types::U128;
     use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{testing_env, AccountId};
+    use near_sdk::{testing_env, AccountId, PromiseResult, RuntimeFeesConfig, VMConfig};
 
     const OWNER_ACCOUNT: usize = 0;
 
@@ -450,7 +1868,7 @@ mod tests {
         testing_env!(context
             .predecessor_account_id(accounts(OWNER_ACCOUNT))
             .build());
-        contract.mt_mint(accounts(OWNER_ACCOUNT), u128::MAX.into())
+        contract.mt_mint(accounts(OWNER_ACCOUNT), u128::MAX.into(), None)
     }
 
     fn deposit_token(
@@ -460,12 +1878,27 @@ mod tests {
         token_id: TokenId,
         amount: Balance,
     ) {
+        let min_balance = contract.storage_balance_bounds().min.0;
         testing_env!(context
             .predecessor_account_id(accounts(OWNER_ACCOUNT))
+            .attached_deposit(min_balance)
             .build());
         contract.mt_transfer(account, token_id, amount.into(), None);
     }
 
+    fn register_account(
+        context: &mut VMContextBuilder,
+        contract: &mut MultiTokenContract,
+        account: AccountId,
+    ) {
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(context
+            .predecessor_account_id(accounts(OWNER_ACCOUNT))
+            .attached_deposit(min_balance)
+            .build());
+        contract.storage_deposit(Some(account), None);
+    }
+
     #[test]
     fn list_asset_balances() {
         let (mut ctx, mut contract) = setup_contract();
@@ -502,6 +1935,199 @@ mod tests {
         assert_eq!(balances[1], 0.into());
     }
 
+    #[test]
+    fn resolve_transfer_handles_partial_use_failure_and_capped_refund() {
+        // Scenario A: the receiver reports leaving part of what it was sent unused - refund
+        // exactly that part back to the sender.
+        let (mut ctx, mut contract) = setup_contract();
+        let token_a = mint_token(&mut ctx, &mut contract);
+        let sender_a = accounts(1);
+        let receiver_a = accounts(2);
+        register_account(&mut ctx, &mut contract, sender_a.clone());
+        deposit_token(
+            &mut ctx,
+            &mut contract,
+            receiver_a.clone(),
+            token_a.token_id.clone(),
+            100,
+        );
+
+        testing_env!(
+            ctx.predecessor_account_id(accounts(OWNER_ACCOUNT))
+                .attached_deposit(0)
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            std::collections::HashMap::default(),
+            vec![PromiseResult::Successful(
+                near_sdk::serde_json::to_vec(&vec![U128(40)]).unwrap()
+            )]
+        );
+        let spent = contract.mt_resolve_transfer(
+            sender_a.clone(),
+            receiver_a.clone(),
+            vec![token_a.token_id.clone()],
+            vec![100.into()],
+        );
+        assert_eq!(spent, vec![60.into()]);
+        assert_eq!(
+            contract.mt_batch_balance_of(receiver_a.clone(), vec![token_a.token_id.clone()]),
+            vec![60.into()]
+        );
+        assert_eq!(
+            contract.mt_batch_balance_of(sender_a.clone(), vec![token_a.token_id.clone()]),
+            vec![40.into()]
+        );
+
+        // Scenario B: the receiver's `mt_on_transfer` promise failed outright - treat the whole
+        // sent amount as unused and refund all of it.
+        let token_b = mint_token(&mut ctx, &mut contract);
+        let sender_b = accounts(3);
+        let receiver_b = accounts(4);
+        register_account(&mut ctx, &mut contract, sender_b.clone());
+        deposit_token(
+            &mut ctx,
+            &mut contract,
+            receiver_b.clone(),
+            token_b.token_id.clone(),
+            100,
+        );
+
+        testing_env!(
+            ctx.predecessor_account_id(accounts(OWNER_ACCOUNT))
+                .attached_deposit(0)
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            std::collections::HashMap::default(),
+            vec![PromiseResult::Failed]
+        );
+        let spent = contract.mt_resolve_transfer(
+            sender_b.clone(),
+            receiver_b.clone(),
+            vec![token_b.token_id.clone()],
+            vec![100.into()],
+        );
+        assert_eq!(spent, vec![0.into()]);
+        assert_eq!(
+            contract.mt_batch_balance_of(receiver_b.clone(), vec![token_b.token_id.clone()]),
+            vec![0.into()]
+        );
+        assert_eq!(
+            contract.mt_batch_balance_of(sender_b.clone(), vec![token_b.token_id.clone()]),
+            vec![100.into()]
+        );
+
+        // Scenario C: the receiver reports more unused than it actually still holds (e.g. it
+        // forwarded part of what it received on to someone else before this callback runs) - cap
+        // the refund at the receiver's real balance instead of underflowing or overpaying.
+        let token_c = mint_token(&mut ctx, &mut contract);
+        let sender_c = accounts(5);
+        // `accounts` only provides six ids, all in use by now, so name the last two directly.
+        let receiver_c: AccountId = "receiver-c".parse().unwrap();
+        let forwardee: AccountId = "forwardee".parse().unwrap();
+        register_account(&mut ctx, &mut contract, sender_c.clone());
+        deposit_token(
+            &mut ctx,
+            &mut contract,
+            receiver_c.clone(),
+            token_c.token_id.clone(),
+            100,
+        );
+
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(ctx
+            .predecessor_account_id(receiver_c.clone())
+            .attached_deposit(min_balance)
+            .build());
+        contract.mt_transfer(forwardee, token_c.token_id.clone(), 70.into(), None);
+
+        testing_env!(
+            ctx.predecessor_account_id(accounts(OWNER_ACCOUNT))
+                .attached_deposit(0)
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            std::collections::HashMap::default(),
+            vec![PromiseResult::Successful(
+                near_sdk::serde_json::to_vec(&vec![U128(90)]).unwrap()
+            )]
+        );
+        let spent = contract.mt_resolve_transfer(
+            sender_c.clone(),
+            receiver_c.clone(),
+            vec![token_c.token_id.clone()],
+            vec![100.into()],
+        );
+        assert_eq!(spent, vec![70.into()]);
+        assert_eq!(
+            contract.mt_batch_balance_of(receiver_c.clone(), vec![token_c.token_id.clone()]),
+            vec![0.into()]
+        );
+        assert_eq!(
+            contract.mt_batch_balance_of(sender_c.clone(), vec![token_c.token_id.clone()]),
+            vec![30.into()]
+        );
+    }
+
+    #[test]
+    fn escrow_or_locks_max_of_branches_and_refunds_leftover() {
+        let (mut ctx, mut contract) = setup_contract();
+        let token = mint_token(&mut ctx, &mut contract);
+        let sender = accounts(1);
+        let receiver = accounts(2);
+
+        deposit_token(
+            &mut ctx,
+            &mut contract,
+            sender.clone(),
+            token.token_id.clone(),
+            1000,
+        );
+
+        // Pays `receiver` once a far-off deadline passes, or refunds `sender` now if `sender`
+        // witnesses first. Only one branch can ever fire, and they want different amounts.
+        let plan = Plan::Or(
+            Box::new((
+                Condition::Timestamp(u64::MAX),
+                Plan::Pay {
+                    receiver: receiver.clone(),
+                    token_ids: vec![token.token_id.clone()],
+                    amounts: vec![U128(100)],
+                },
+            )),
+            Box::new((
+                Condition::Witness(sender.clone()),
+                Plan::Pay {
+                    receiver: sender.clone(),
+                    token_ids: vec![token.token_id.clone()],
+                    amounts: vec![U128(40)],
+                },
+            )),
+        );
+
+        testing_env!(ctx.predecessor_account_id(sender.clone()).build());
+        let plan_id = contract.create_escrow(plan);
+
+        // Only the max reachable amount (100, not the 100+40 sum of both branches) gets locked.
+        assert_eq!(
+            contract.mt_batch_balance_of(sender.clone(), vec![token.token_id.clone()]),
+            vec![900.into()]
+        );
+
+        // The deadline hasn't passed, so the witness branch resolves for `sender`.
+        testing_env!(ctx.predecessor_account_id(sender.clone()).build());
+        contract.apply_witness(plan_id);
+
+        // `sender` gets its 40-token payout plus the 60 leftover the losing branch would have
+        // needed, landing right back at its starting balance - nothing stranded in the contract.
+        assert_eq!(
+            contract.mt_batch_balance_of(sender.clone(), vec![token.token_id.clone()]),
+            vec![1000.into()]
+        );
+        assert!(contract.get_escrow(plan_id).is_none());
+    }
+
     // TODO: For the below use-cases we need to implement some sort of a contract, similar to
     // https://github.com/near/near-sdk-rs/blob/d996fc433c4d059fc99ee9ffcdff29870c3e87da/examples/multi-token/test-contract-defi/src/lib.rs#L1-L0.
     // TODO: Add a use-case when one token is exchanged for another, using mt_transfer_call.
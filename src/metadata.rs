@@ -0,0 +1,46 @@
+use crate::token::TokenId;
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// Spec string for this contract's metadata, following the `"{standard}-{version}"` convention
+/// used by NEP-148/NEP-177 (`"ft-1.0.0"`, `"nft-1.0.0"`).
+pub const MT_METADATA_SPEC: &str = "mt-1.0.0";
+
+/// Contract-level metadata, analogous to `FungibleTokenMetadata`/`NFTContractMetadata`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "abi", derive(schemars::JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtContractMetadata {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>,
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+/// Per-token metadata, stored at mint time and keyed by `TokenId`. `decimals` in particular is
+/// what lets a client tell a fungible-style token (`decimals > 0`) apart from an NFT-style one
+/// (`decimals == 0`) and display amounts correctly.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[cfg_attr(feature = "abi", derive(schemars::JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMetadata {
+    pub name: String,
+    pub symbol: Option<String>,
+    pub decimals: u8,
+    pub icon: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+pub trait MultiTokenMetadataProvider {
+    /// Returns the contract-level metadata.
+    fn mt_metadata(&self) -> MtContractMetadata;
+
+    /// Returns per-token metadata for each of `token_ids`, `None` where a token has none set.
+    fn mt_token_metadata(&self, token_ids: Vec<TokenId>) -> Vec<Option<TokenMetadata>>;
+}
@@ -0,0 +1,53 @@
+use crate::Balance;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+/// Cost of a single byte of contract storage, in yoctoNEAR. Matches the value used by the NEP-141
+/// reference implementation (`w-near`/FT core) at the time of writing.
+pub const STORAGE_PRICE_PER_BYTE: Balance = 10_000_000_000_000_000_000;
+
+/// Longest account id NEAR allows; used to measure a worst-case `accounts_storage` entry.
+pub const ACCOUNT_ID_MAX_LENGTH: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "abi", derive(schemars::JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "abi", derive(schemars::JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+/// Port of the NEP-145 storage-management surface, gating per-account storage so that holding a
+/// balance in `balances_per_token`/`tokens_per_owner` requires a deposit up front.
+pub trait StorageManagement {
+    /// Registers `account_id` (defaulting to the caller) or tops up its existing registration,
+    /// using the attached deposit.
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance;
+
+    /// Withdraws `amount` (defaulting to everything available) of the caller's storage deposit
+    /// above the minimum required balance; panics if `amount` exceeds what is available. Requires
+    /// exactly 1 yoctoNEAR attached.
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance;
+
+    /// Unregisters the caller, refunding its storage deposit. Fails if the account still holds a
+    /// non-zero balance of any token unless `force` is `true`, in which case those balances are
+    /// burned. Requires exactly 1 yoctoNEAR attached.
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool;
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds;
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance>;
+}
@@ -1,3 +1,4 @@
+use crate::metadata::TokenMetadata;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::AccountId;
 
@@ -12,8 +13,12 @@ pub struct Token {
     // Question: what do we need this for? Logically the owner of the token is somebody who has the
     // control of it's supply when it's minted. Once those tokens start being transferred to other
     // accounts - this field is basically irrelevant.
-    // If we want to keep track of the original owner - that could be done via events/metadata.
+    // The original owner is tracked this way: `mt_mint` emits an `MtMint` NEP-297 event carrying
+    // `owner_id`, so indexers can recover it without reading this field off-chain.
     pub owner_id: AccountId,
     /// Total amount generated
     pub supply: u128,
+    /// Metadata supplied at mint time, if any. `decimals` is what lets a client tell apart a
+    /// fungible-style token from an NFT-style one.
+    pub metadata: Option<TokenMetadata>,
 }